@@ -2,7 +2,7 @@
 
 use super::balancer::BalancerFactory;
 use super::connector::{ConfigError as ConnectorConfigError, ConnectorFactoryConfig};
-use super::resolver::{ConfigError as ResolverConfigError, NamerdConfig};
+use super::resolver::{ConfigError as ResolverConfigError, DnsConfig, NamerdConfig};
 use super::server::ConfigError as ServerConfigError;
 use super::{admin, resolver, router, server};
 use futures::{sync, Future, Stream};
@@ -206,9 +206,17 @@ impl RouterConfig {
         // router. The resolver executor is used to drive execution in another thread.
         let (resolver, resolver_exec) = match self.interpreter {
             InterpreterConfig::NamerdHttp(config) => {
-                let namerd = config.into_namerd(&metrics).map_err(Error::Interpreter)?;
+                let namerd = config
+                    .into_namerd(&metrics)
+                    .map_err(|e| Error::Interpreter(ResolverConfigError::Namerd(e)))?;
                 resolver::new(namerd)
             }
+            InterpreterConfig::Dns(config) => {
+                let dns = config
+                    .into_dns(&metrics)
+                    .map_err(|e| Error::Interpreter(ResolverConfigError::Dns(e)))?;
+                resolver::new_dns(dns)
+            }
         };
 
         let balancer = {
@@ -263,14 +271,16 @@ impl RouterSpawner {
 }
 
 /// Configures an interpreter.
-///
-/// Currently, only the io.l5d.namerd.http interpreter is supported.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, tag = "kind")]
 pub enum InterpreterConfig {
     /// Polls namerd for updates.
     #[serde(rename = "io.l5d.namerd.http")]
     NamerdHttp(NamerdConfig),
+
+    /// Resolves a DNS name on an interval.
+    #[serde(rename = "io.l5d.dns")]
+    Dns(DnsConfig),
 }
 
 /// Configures the admin server.
@@ -0,0 +1,400 @@
+//! Interprets destinations by resolving a DNS name on an interval.
+//!
+//! Unlike the namerd interpreter, which learns of individual address
+//! changes as they happen, a DNS lookup only ever returns the complete,
+//! current set of records for a name. So rather than diffing the response
+//! into incremental `Update::Added`/`Update::Removed` events, this
+//! interpreter emits the entire resolved set as a single `Update::Pool`,
+//! replacing the router's address pool wholesale. This keeps a transient
+//! resolution failure from being able to drain a pool one address at a
+//! time; instead, the previous pool is held in place until either a fresh
+//! answer arrives or `failure_grace_secs` of continuous failures elapses.
+
+use std::collections::HashMap;
+use std::net;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, loop_fn, Loop};
+use futures::sync::mpsc::UnboundedSender;
+use futures::Future;
+use tacho;
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use trust_dns_resolver::error::ResolveErrorKind;
+use trust_dns_resolver::ResolverFuture;
+
+use super::{Update, WeightedAddr};
+
+const DEFAULT_REFRESH_SECS: u64 = 5;
+const DEFAULT_FAILURE_GRACE_SECS: u64 = 30;
+
+/// Configures the `io.l5d.dns` interpreter.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct DnsConfig {
+    /// The hostname to resolve.
+    pub host: String,
+
+    /// The port to pair with each resolved address.
+    ///
+    /// Ignored when `srv` is set, since SRV records carry their own port.
+    pub port: u16,
+
+    /// The minimum number of seconds between lookups. The record TTL
+    /// returned by the server is used instead whenever it's larger, so a
+    /// short-lived record is never re-queried before it expires.
+    pub refresh_secs: Option<u64>,
+
+    /// When true, `host` is resolved as a SRV name: each record's target,
+    /// port, and weight are used directly instead of `port` and a uniform
+    /// weight.
+    pub srv: Option<bool>,
+
+    /// How many seconds of continuous lookup failure (NXDOMAIN, SERVFAIL,
+    /// timeouts, etc) are tolerated before the pool is drained. Until this
+    /// elapses, the last-known-good pool is left in place.
+    pub failure_grace_secs: Option<u64>,
+}
+
+/// Describes a misconfigured DNS interpreter.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// `host` was empty.
+    InvalidHost,
+}
+
+impl DnsConfig {
+    /// Validates this configuration, producing a `Dns` interpreter.
+    pub fn into_dns(self, metrics: &tacho::Scope) -> Result<Dns, ConfigError> {
+        if self.host.is_empty() {
+            return Err(ConfigError::InvalidHost);
+        }
+        Ok(Dns {
+            config: self,
+            metrics: super::metrics(metrics),
+        })
+    }
+}
+
+/// Resolves a DNS name on an interval, replacing the address pool wholesale.
+pub struct Dns {
+    config: DnsConfig,
+    metrics: super::Metrics,
+}
+
+impl Dns {
+    pub(super) fn executor(self, tx: UnboundedSender<Update>) -> Executor {
+        Executor {
+            dns: self,
+            tx,
+            pool: Vec::new(),
+            failing_since: None,
+        }
+    }
+}
+
+/// Drives DNS resolution on the admin reactor.
+pub struct Executor {
+    dns: Dns,
+    tx: UnboundedSender<Update>,
+    pool: Vec<WeightedAddr>,
+    failing_since: Option<Instant>,
+}
+
+impl Executor {
+    /// Runs this executor to completion on the provided reactor.
+    ///
+    /// Never itself resolves successfully: it loops, re-resolving `host`
+    /// every `refresh_secs` (or the record TTL, whichever is larger) until
+    /// the process shuts down.
+    pub fn execute(self, handle: &Handle, timer: &Timer) -> Box<Future<Item = (), Error = ()>> {
+        let resolver = match ResolverFuture::from_system_conf(handle) {
+            Ok(resolver) => resolver,
+            Err(e) => {
+                error!("failed to initialize DNS resolver: {}", e);
+                return Box::new(future::err(()));
+            }
+        };
+        let timer = timer.clone();
+
+        let fut = loop_fn(self, move |state| {
+            let resolver = resolver.clone();
+            let timer = timer.clone();
+            resolve_once(state, &resolver).and_then(move |(state, wait)| {
+                timer
+                    .sleep(wait)
+                    .map_err(|_| ())
+                    .map(move |_| Loop::Continue(state))
+            })
+        });
+        Box::new(fut)
+    }
+}
+
+/// Performs one resolution attempt, returning the (possibly-unchanged)
+/// executor state and how long to wait before the next attempt.
+fn resolve_once(
+    mut state: Executor,
+    resolver: &ResolverFuture,
+) -> Box<Future<Item = (Executor, Duration), Error = ()>> {
+    let refresh = Duration::from_secs(
+        state
+            .dns
+            .config
+            .refresh_secs
+            .unwrap_or(DEFAULT_REFRESH_SECS),
+    );
+    let grace = Duration::from_secs(
+        state
+            .dns
+            .config
+            .failure_grace_secs
+            .unwrap_or(DEFAULT_FAILURE_GRACE_SECS),
+    );
+
+    let lookup: Box<Future<Item = (Vec<WeightedAddr>, Duration), Error = ResolveErrorKind>> =
+        if state.dns.config.srv.unwrap_or(false) {
+            Box::new(lookup_srv(resolver, &state.dns.config.host))
+        } else {
+            Box::new(lookup_ip(
+                resolver,
+                &state.dns.config.host,
+                state.dns.config.port,
+            ))
+        };
+
+    let fut = lookup.then(move |result| {
+        match result {
+            Ok((mut addrs, ttl)) => {
+                state.failing_since = None;
+                // Sort so that a nameserver merely reordering the same
+                // record set (e.g. round-robin rotation) isn't mistaken
+                // for a pool change.
+                addrs.sort_by_key(|a| a.addr);
+                if addrs != state.pool {
+                    state.pool = addrs.clone();
+                    state.dns.metrics.endpoints.set(addrs.len() as u64);
+                    let _ = state.tx.unbounded_send(Update::Pool(addrs));
+                }
+                let wait = ::std::cmp::max(refresh, ttl);
+                Ok((state, wait))
+            }
+            Err(e) => {
+                state.dns.metrics.errors.incr(1);
+                warn!("failed to resolve {}: {:?}", state.dns.config.host, e);
+
+                let now = Instant::now();
+                if drain_after_grace(&mut state.pool, &mut state.failing_since, now, grace) {
+                    warn!(
+                        "{} has failed to resolve for over {:?}; draining pool",
+                        state.dns.config.host, grace
+                    );
+                    state.dns.metrics.endpoints.set(0);
+                    let _ = state.tx.unbounded_send(Update::Pool(Vec::new()));
+                }
+                Ok((state, refresh))
+            }
+        }
+    });
+
+    Box::new(fut)
+}
+
+/// Tracks how long `host` has failed to resolve. Once `grace` has elapsed
+/// since the first consecutive failure, drains `pool` and returns `true`
+/// (so the caller emits the drain exactly once). Returns `false` while
+/// still within grace, or if `pool` is already empty (so a prior drain, or
+/// a name with no addresses to begin with, isn't re-announced every tick).
+fn drain_after_grace(
+    pool: &mut Vec<WeightedAddr>,
+    failing_since: &mut Option<Instant>,
+    now: Instant,
+    grace: Duration,
+) -> bool {
+    let since = *failing_since.get_or_insert(now);
+    if pool.is_empty() || now.duration_since(since) < grace {
+        return false;
+    }
+    pool.clear();
+    true
+}
+
+/// Returns how long until `until`, or zero if it's already passed.
+///
+/// `trust_dns_resolver`'s lookups expose their TTL as a deadline
+/// (`valid_until() -> Instant`) rather than a duration, so the remaining
+/// time has to be computed relative to now.
+fn remaining(until: Instant) -> Duration {
+    let now = Instant::now();
+    if until > now {
+        until - now
+    } else {
+        Duration::from_secs(0)
+    }
+}
+
+/// Resolves `host` as A/AAAA records, pairing each address with `port` and
+/// an equal weight, and returns the TTL of the response.
+fn lookup_ip(
+    resolver: &ResolverFuture,
+    host: &str,
+    port: u16,
+) -> Box<Future<Item = (Vec<WeightedAddr>, Duration), Error = ResolveErrorKind>> {
+    let fut = resolver
+        .lookup_ip(host)
+        .map_err(|e| e.kind().clone())
+        .map(move |lookup| {
+            let ttl = remaining(lookup.valid_until());
+            let addrs = lookup
+                .iter()
+                .map(|ip| WeightedAddr::new(net::SocketAddr::new(ip, port)))
+                .collect();
+            (addrs, ttl)
+        });
+    Box::new(fut)
+}
+
+/// Resolves `host` as SRV records, then resolves each distinct target
+/// hostname as A/AAAA records, pairing the resulting addresses with that
+/// record's own port/weight.
+///
+/// SRV records (RFC 2782) only ever carry a target hostname, priority,
+/// weight, and port — never a resolved address, and most real-world
+/// responses (most DNS providers, headless k8s services, Consul) don't
+/// include "additional section" glue either. So a target lookup is always
+/// required.
+fn lookup_srv(
+    resolver: &ResolverFuture,
+    host: &str,
+) -> Box<Future<Item = (Vec<WeightedAddr>, Duration), Error = ResolveErrorKind>> {
+    let resolver = resolver.clone();
+    let fut = resolver
+        .lookup_srv(host)
+        .map_err(|e| e.kind().clone())
+        .and_then(move |lookup| {
+            let ttl = remaining(lookup.valid_until());
+            let records: Vec<(String, u16, f32)> = lookup
+                .iter()
+                .map(|srv| (srv.target().to_utf8(), srv.port(), f32::from(srv.weight())))
+                .collect();
+
+            let mut targets: Vec<String> = records.iter().map(|(t, _, _)| t.clone()).collect();
+            targets.sort();
+            targets.dedup();
+
+            // A single stale/unresolvable target shouldn't fail resolution
+            // of the other, healthy targets, so lookup failures are logged
+            // and treated as "no addresses" rather than propagated.
+            let target_lookups = targets.into_iter().map(move |target| {
+                let failed_target = target.clone();
+                resolver
+                    .lookup_ip(target.as_str())
+                    .map(move |ips| (target, ips.iter().collect::<Vec<_>>()))
+                    .or_else(move |e| -> Result<(String, Vec<net::IpAddr>), ResolveErrorKind> {
+                        warn!("failed to resolve SRV target {}: {}", failed_target, e);
+                        Ok((failed_target, Vec::new()))
+                    })
+            });
+
+            future::join_all(target_lookups).map(move |resolved| {
+                let ips_by_target: HashMap<String, Vec<net::IpAddr>> =
+                    resolved.into_iter().collect();
+                let addrs = records
+                    .into_iter()
+                    .flat_map(|(target, port, weight)| {
+                        ips_by_target
+                            .get(&target)
+                            .cloned()
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(move |ip| WeightedAddr {
+                                addr: net::SocketAddr::new(ip, port),
+                                weight,
+                            })
+                    })
+                    .collect();
+                (addrs, ttl)
+            })
+        });
+    Box::new(fut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> net::SocketAddr {
+        net::SocketAddr::new(net::IpAddr::from([127, 0, 0, 1]), port)
+    }
+
+    #[test]
+    fn remaining_is_positive_for_a_future_instant() {
+        let now = Instant::now();
+        let until = now + Duration::from_secs(30);
+        let ttl = remaining(until);
+        assert!(ttl > Duration::from_secs(0));
+        assert!(ttl <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn remaining_is_zero_for_a_past_instant() {
+        let now = Instant::now();
+        // `now` itself is already in the past by the time `remaining` reads
+        // the clock again, so this covers both "equal to" and "before".
+        assert_eq!(remaining(now), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn sorting_makes_reordered_pools_compare_equal() {
+        let mut a = vec![
+            WeightedAddr::new(addr(2)),
+            WeightedAddr::new(addr(1)),
+            WeightedAddr::new(addr(3)),
+        ];
+        let mut b = vec![
+            WeightedAddr::new(addr(3)),
+            WeightedAddr::new(addr(1)),
+            WeightedAddr::new(addr(2)),
+        ];
+        assert_ne!(a, b, "fixture should start out differently ordered");
+        a.sort_by_key(|w| w.addr);
+        b.sort_by_key(|w| w.addr);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn drain_after_grace_waits_for_the_full_grace_period() {
+        let mut pool = vec![WeightedAddr::new(addr(1))];
+        let mut failing_since = None;
+        let grace = Duration::from_secs(30);
+        let start = Instant::now();
+
+        // First failure: starts the clock, doesn't drain yet.
+        assert!(!drain_after_grace(&mut pool, &mut failing_since, start, grace));
+        assert_eq!(pool.len(), 1);
+
+        // Still within grace.
+        let mid = start + Duration::from_secs(29);
+        assert!(!drain_after_grace(&mut pool, &mut failing_since, mid, grace));
+        assert_eq!(pool.len(), 1);
+
+        // Grace has elapsed: drains exactly once.
+        let after = start + Duration::from_secs(31);
+        assert!(drain_after_grace(&mut pool, &mut failing_since, after, grace));
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn drain_after_grace_does_not_redrain_an_already_empty_pool() {
+        let mut pool = Vec::new();
+        let mut failing_since = Some(Instant::now() - Duration::from_secs(60));
+        let grace = Duration::from_secs(30);
+
+        assert!(!drain_after_grace(
+            &mut pool,
+            &mut failing_since,
+            Instant::now(),
+            grace
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+//! Interprets router destinations into a stream of address pool updates.
+//!
+//! An interpreter is configured by `app::InterpreterConfig` and split into a
+//! `Resolver`, which the router consumes to learn about the current address
+//! pool, and an `Executor`, which is driven on the admin thread's reactor so
+//! that service discovery lookups never block serving.
+
+use std::net;
+
+use futures::sync::mpsc;
+use futures::{Future, Poll, Stream};
+use tacho;
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+
+pub mod dns;
+pub mod namerd;
+
+pub use self::dns::DnsConfig;
+pub use self::namerd::NamerdConfig;
+
+/// Describes a misconfigured interpreter.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The namerd interpreter was misconfigured.
+    Namerd(namerd::ConfigError),
+
+    /// The DNS interpreter was misconfigured.
+    Dns(dns::ConfigError),
+}
+
+/// A weighted destination address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedAddr {
+    /// The destination address.
+    pub addr: net::SocketAddr,
+
+    /// The relative weight of this address within the pool.
+    pub weight: f32,
+}
+
+impl WeightedAddr {
+    /// Creates a `WeightedAddr` with the default weight of `1.0`.
+    pub fn new(addr: net::SocketAddr) -> WeightedAddr {
+        WeightedAddr { addr, weight: 1.0 }
+    }
+}
+
+/// Describes a change to a router's address pool.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Update {
+    /// Adds a single address to the pool.
+    Added(WeightedAddr),
+
+    /// Removes a single address from the pool.
+    Removed(net::SocketAddr),
+
+    /// Replaces the entire pool with this set of addresses.
+    ///
+    /// Used by interpreters whose source of truth is refreshed as a complete
+    /// snapshot rather than a diff (e.g. DNS), so that a transient
+    /// resolution failure can't be mistaken for every backend disappearing.
+    Pool(Vec<WeightedAddr>),
+}
+
+/// A stream of address pool updates consumed by a router.
+pub struct Resolver(mpsc::UnboundedReceiver<Update>);
+
+impl Stream for Resolver {
+    type Item = Update;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Update>, ()> {
+        self.0.poll()
+    }
+}
+
+/// Drives an interpreter's background work (polling, DNS lookups, etc).
+pub enum Executor {
+    /// Drives a namerd interpreter.
+    Namerd(namerd::Executor),
+
+    /// Drives a DNS interpreter.
+    Dns(dns::Executor),
+}
+
+impl Executor {
+    /// Runs this executor to completion on the provided reactor.
+    pub fn execute(
+        self,
+        handle: &Handle,
+        timer: &Timer,
+    ) -> Box<Future<Item = (), Error = ()>> {
+        match self {
+            Executor::Namerd(e) => Box::new(e.execute(handle, timer)),
+            Executor::Dns(e) => Box::new(e.execute(handle, timer)),
+        }
+    }
+}
+
+/// Creates the update channel for an interpreter: the sender is handed to
+/// that interpreter's executor, and the `Resolver` wraps the receiver.
+fn channel() -> (mpsc::UnboundedSender<Update>, Resolver) {
+    let (tx, rx) = mpsc::unbounded();
+    (tx, Resolver(rx))
+}
+
+/// Creates a `(Resolver, Executor)` pair for a namerd-backed interpreter.
+pub fn new(namerd: namerd::Namerd) -> (Resolver, Executor) {
+    let (tx, resolver) = channel();
+    (resolver, Executor::Namerd(namerd.executor(tx)))
+}
+
+/// Creates a `(Resolver, Executor)` pair for a DNS-backed interpreter.
+pub fn new_dns(dns: dns::Dns) -> (Resolver, Executor) {
+    let (tx, resolver) = channel();
+    (resolver, Executor::Dns(dns.executor(tx)))
+}
+
+/// Metrics shared by all interpreter implementations.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    pub endpoints: tacho::Gauge,
+    pub errors: tacho::Counter,
+}
+
+pub(crate) fn metrics(scope: &tacho::Scope) -> Metrics {
+    let scope = scope.clone().prefixed("interpreter");
+    Metrics {
+        endpoints: scope.gauge("endpoints"),
+        errors: scope.counter("errors"),
+    }
+}
@@ -0,0 +1,231 @@
+//! Interprets destinations by polling namerd's HTTP delegation API.
+
+use std::collections::HashMap;
+use std::net;
+use std::time::Duration;
+
+use futures::future::Either;
+use futures::sync::mpsc::UnboundedSender;
+use futures::{Future, Stream};
+use hyper::{Client, Uri};
+use serde_json;
+use tacho;
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+
+use super::{Update, WeightedAddr};
+
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// Configures the `io.l5d.namerd.http` interpreter.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct NamerdConfig {
+    /// The base URL of namerd's HTTP delegation API, e.g.
+    /// `http://namerd.io:4180`.
+    pub url: String,
+
+    /// The namerd namespace to resolve against.
+    pub namespace: String,
+
+    /// The logical name to resolve, e.g. `/svc/foo`.
+    pub target: String,
+
+    /// How often to poll namerd for updates. Defaults to 5 seconds.
+    pub interval_secs: Option<u64>,
+}
+
+/// Describes a misconfigured namerd interpreter.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configured URL could not be parsed.
+    InvalidUrl(String),
+}
+
+impl NamerdConfig {
+    /// Validates this configuration, producing a `Namerd` interpreter.
+    pub fn into_namerd(self, metrics: &tacho::Scope) -> Result<Namerd, ConfigError> {
+        let interval = Duration::from_secs(self.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS));
+        let uri = resolve_uri(&self.url, &self.namespace, &self.target)
+            .parse::<Uri>()
+            .map_err(|_| ConfigError::InvalidUrl(self.url.clone()))?;
+        Ok(Namerd {
+            config: self,
+            uri,
+            interval,
+            metrics: super::metrics(metrics),
+        })
+    }
+}
+
+fn resolve_uri(base: &str, namespace: &str, target: &str) -> String {
+    format!(
+        "{}/api/1/resolve/{}?path={}",
+        base.trim_end_matches('/'),
+        namespace,
+        target
+    )
+}
+
+/// Polls namerd on an interval, emitting incremental pool updates.
+pub struct Namerd {
+    config: NamerdConfig,
+    uri: Uri,
+    interval: Duration,
+    metrics: super::Metrics,
+}
+
+impl Namerd {
+    pub(super) fn executor(self, tx: UnboundedSender<Update>) -> Executor {
+        Executor {
+            namerd: self,
+            tx,
+            pool: HashMap::new(),
+        }
+    }
+}
+
+/// Drives namerd polling on the admin reactor.
+pub struct Executor {
+    namerd: Namerd,
+    tx: UnboundedSender<Update>,
+    pool: HashMap<net::SocketAddr, f32>,
+}
+
+impl Executor {
+    /// Runs this executor to completion on the provided reactor.
+    ///
+    /// On each tick, re-resolves `target` against namerd's delegation API
+    /// and diffs the response against the previously-seen pool, emitting
+    /// `Update::Added`/`Update::Removed` for the difference. A failed or
+    /// unparseable response is logged and otherwise ignored, leaving the
+    /// existing pool in place until the next tick.
+    pub fn execute(self, handle: &Handle, timer: &Timer) -> Box<Future<Item = (), Error = ()>> {
+        let Executor {
+            namerd,
+            mut tx,
+            mut pool,
+        } = self;
+
+        info!(
+            "resolving {} in namespace {} via namerd at {}",
+            namerd.config.target, namerd.config.namespace, namerd.config.url
+        );
+
+        let client = Client::new(handle);
+        let uri = namerd.uri;
+        let metrics = namerd.metrics;
+        let target = namerd.config.target;
+
+        let request_timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+        let sleep_timer = timer.clone();
+        let poll = timer.interval(namerd.interval).map_err(|_| {}).for_each(move |_| {
+            let target = target.clone();
+            let request = client
+                .get(uri.clone())
+                .and_then(|res| res.body().concat2())
+                .map_err(|_| ());
+            let timeout = sleep_timer.clone().sleep(request_timeout).map_err(|_| ());
+
+            // Races the request against a timeout so that a namerd that
+            // accepts a connection but never responds can't freeze polling
+            // indefinitely.
+            request.select2(timeout).then(move |result| -> Result<(), ()> {
+                match result {
+                    Ok(Either::A((body, _))) => {
+                        match serde_json::from_slice::<NamerdResponse>(&body) {
+                            Ok(resp) => apply_response(resp, &mut pool, &mut tx, &metrics),
+                            Err(e) => {
+                                warn!("failed to parse namerd response for {}: {}", target, e)
+                            }
+                        }
+                    }
+                    Ok(Either::B(_)) => {
+                        warn!(
+                            "namerd request for {} timed out after {:?}",
+                            target, request_timeout
+                        );
+                    }
+                    Err(Either::A(_)) => warn!("failed to resolve {} via namerd", target),
+                    Err(Either::B(_)) => warn!("namerd request timer failed for {}", target),
+                }
+                Ok(())
+            })
+        });
+
+        Box::new(poll)
+    }
+}
+
+/// A parsed namerd delegation API response.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum NamerdResponse {
+    /// The name is bound to a concrete set of addresses.
+    #[serde(rename = "bound")]
+    Bound { addrs: Vec<BoundAddr> },
+
+    /// The name resolved to nothing.
+    #[serde(rename = "neg")]
+    Neg,
+
+    /// Resolution failed on the namerd side.
+    #[serde(rename = "failed")]
+    Failed { message: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct BoundAddr {
+    ip: net::IpAddr,
+    port: u16,
+    meta: Option<BoundAddrMeta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BoundAddrMeta {
+    #[serde(rename = "endpointAddrWeight")]
+    endpoint_addr_weight: Option<f32>,
+}
+
+/// Diffs a namerd response against the previously-seen `pool`, sending
+/// `Update::Added`/`Update::Removed` for each address that changed.
+fn apply_response(
+    resp: NamerdResponse,
+    pool: &mut HashMap<net::SocketAddr, f32>,
+    tx: &mut UnboundedSender<Update>,
+    metrics: &super::Metrics,
+) {
+    let resolved: HashMap<net::SocketAddr, f32> = match resp {
+        NamerdResponse::Bound { addrs } => addrs
+            .into_iter()
+            .map(|a| {
+                let weight = a.meta.and_then(|m| m.endpoint_addr_weight).unwrap_or(1.0);
+                (net::SocketAddr::new(a.ip, a.port), weight)
+            })
+            .collect(),
+        NamerdResponse::Neg => HashMap::new(),
+        NamerdResponse::Failed { message } => {
+            warn!("namerd resolution failed: {}", message);
+            metrics.errors.incr(1);
+            return;
+        }
+    };
+
+    for (addr, weight) in &resolved {
+        if pool.get(addr) != Some(weight) {
+            let _ = tx.unbounded_send(Update::Added(WeightedAddr {
+                addr: *addr,
+                weight: *weight,
+            }));
+        }
+    }
+    for addr in pool.keys() {
+        if !resolved.contains_key(addr) {
+            let _ = tx.unbounded_send(Update::Removed(*addr));
+        }
+    }
+
+    metrics.endpoints.set(resolved.len() as u64);
+    *pool = resolved;
+}